@@ -0,0 +1,310 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Capability-based access control for caucuses, with attenuation.
+//!
+//! A [`Cap`] pairs a reference to a [`Caucus`] with a chain of
+//! caveats, where a caveat is just anything implementing
+//! [`Guard<A, Operation, CapContext<A, M>>`]. Holding a `Cap` grants
+//! exactly the authority described by its caveat chain: every caveat
+//! must permit an operation before it is allowed. [`Cap::attenuate`]
+//! lets a holder mint a strictly weaker capability without
+//! consulting whoever granted it the original one, since a caveat
+//! can only narrow what is allowed, never widen it.
+
+use crate::access_control::{AccessDenied, Guard};
+
+use super::{Caucus, CaucusGuard, Operation, ReceiveContext, ReceiveGuard};
+
+/// What a caveat is consulted with: the caucus itself, plus the
+/// candidate message the operation concerns, when there is one (e.g.
+/// the message about to be sent or received; `None` when checked
+/// through [`Cap::attempt`]). This
+/// is what lets a caveat express "may only Send messages matching
+/// predicate P".
+pub struct CapContext<'a, A, M> {
+    /// The caucus the capability was minted over.
+    pub caucus: &'a Caucus<A, M>,
+    /// The message the attempted operation concerns, if any.
+    pub message: Option<&'a M>,
+}
+
+/// A single caveat in a [`Cap`]'s chain: a checked predicate that
+/// must permit an operation before it is allowed. Generic over the
+/// context's lifetime so a caveat can be checked against a message
+/// borrowed only for the duration of one `attempt` call.
+type Caveat<'a, A, M> = Box<dyn for<'r> Guard<A, Operation, CapContext<'r, A, M>> + 'a>;
+
+/// A reference to a [`Caucus`] together with a chain of caveats that
+/// must all permit an operation.
+pub struct Cap<'a, A, M> {
+    caucus: &'a Caucus<A, M>,
+    caveats: Vec<Caveat<'a, A, M>>,
+}
+
+impl<'a, A, M> Cap<'a, A, M> {
+    /// Mint a capability over `caucus` with no caveats yet, i.e. one
+    /// that permits every operation. The caucus's own guards are not
+    /// consulted unless added as caveats (see [`Cap::from_guard`]).
+    pub fn new(caucus: &'a Caucus<A, M>) -> Self {
+        Cap {
+            caucus,
+            caveats: Vec::new(),
+        }
+    }
+
+    /// Build a capability out of one of `caucus`'s own guards, e.g.
+    /// `Cap::from_guard(&caucus, &caucus.send_guard)`. This is the
+    /// adapter that lets `join_guard`/`send_guard` be expressed as
+    /// capabilities; `receive_guard` has a different, message-aware
+    /// shape (`ReceiveGuard`), so use [`Cap::from_receive_guard`] for
+    /// that one instead.
+    pub fn from_guard(caucus: &'a Caucus<A, M>, guard: &'a CaucusGuard<A, M>) -> Self
+    where
+        A: 'a,
+        M: 'a,
+    {
+        Cap::new(caucus).attenuate(GuardCaveat(guard))
+    }
+
+    /// Build a capability out of `caucus.receive_guard`, e.g.
+    /// `Cap::from_receive_guard(&caucus, &caucus.receive_guard)`.
+    /// Unlike [`Cap::from_guard`], the resulting caveat needs the
+    /// candidate message to check against, so it only permits
+    /// `Operation::Receive` when consulted through
+    /// [`Cap::attempt_receive`].
+    pub fn from_receive_guard(caucus: &'a Caucus<A, M>, guard: &'a ReceiveGuard<A, M>) -> Self
+    where
+        A: 'a,
+        M: 'a,
+    {
+        Cap::new(caucus).attenuate(ReceiveGuardCaveat(guard))
+    }
+
+    /// Mint a strictly weaker capability by appending `caveat` to the
+    /// chain. Because attenuation can only add a restriction, never
+    /// remove one, the holder never needs to consult the grantor to
+    /// do this.
+    pub fn attenuate<C>(mut self, caveat: C) -> Self
+    where
+        C: for<'r> Guard<A, Operation, CapContext<'r, A, M>> + 'a,
+    {
+        self.caveats.push(Box::new(caveat));
+        self
+    }
+
+    /// Check `actor` attempting `op` with no particular message in
+    /// mind (e.g. `Join`/`Receive`), running every caveat in the
+    /// chain and short-circuiting on the first denial.
+    pub fn attempt(&self, actor: &A, op: Operation) -> Result<(), AccessDenied> {
+        self.attempt_message(actor, op, None)
+    }
+
+    /// Check `actor` attempting to `Send` `message`, running every
+    /// caveat in the chain with the message attached so caveats like
+    /// "may only Send messages matching predicate P" can inspect it.
+    pub fn attempt_send(&self, actor: &A, message: &M) -> Result<(), AccessDenied> {
+        self.attempt_message(actor, Operation::Send, Some(message))
+    }
+
+    /// Check `actor` attempting to `Receive` `message`, running every
+    /// caveat in the chain with the message attached so a caveat
+    /// adapted from a message-aware `receive_guard` (via
+    /// [`Cap::from_receive_guard`]) can inspect it.
+    pub fn attempt_receive(&self, actor: &A, message: &M) -> Result<(), AccessDenied> {
+        self.attempt_message(actor, Operation::Receive, Some(message))
+    }
+
+    fn attempt_message(
+        &self,
+        actor: &A,
+        op: Operation,
+        message: Option<&M>,
+    ) -> Result<(), AccessDenied> {
+        let ctx = CapContext {
+            caucus: self.caucus,
+            message,
+        };
+        for caveat in &self.caveats {
+            caveat.attempt(actor, op, &ctx)?;
+        }
+        Ok(())
+    }
+}
+
+/// Adapts a borrowed [`CaucusGuard`] into a caveat that doesn't care
+/// about the candidate message.
+struct GuardCaveat<'a, A, M>(&'a CaucusGuard<A, M>);
+
+impl<'a, A, M> Guard<A, Operation, CapContext<'_, A, M>> for GuardCaveat<'a, A, M> {
+    fn attempt(
+        &self,
+        actor: &A,
+        op: Operation,
+        ctx: &CapContext<'_, A, M>,
+    ) -> Result<(), AccessDenied> {
+        self.0.attempt(actor, op, ctx.caucus)
+    }
+}
+
+/// Adapts a borrowed [`ReceiveGuard`] into a caveat, checking the
+/// candidate message via [`ReceiveContext`] like `receive_guard`
+/// itself would. Denies if consulted without a message, since
+/// there's nothing to build a `ReceiveContext` from.
+struct ReceiveGuardCaveat<'a, A, M>(&'a ReceiveGuard<A, M>);
+
+impl<'a, A, M> Guard<A, Operation, CapContext<'_, A, M>> for ReceiveGuardCaveat<'a, A, M> {
+    fn attempt(
+        &self,
+        actor: &A,
+        op: Operation,
+        ctx: &CapContext<'_, A, M>,
+    ) -> Result<(), AccessDenied> {
+        match ctx.message {
+            Some(message) => {
+                let receive_ctx = ReceiveContext {
+                    caucus: ctx.caucus,
+                    message,
+                };
+                self.0.attempt(actor, op, &receive_ctx)
+            }
+            None => Err(AccessDenied),
+        }
+    }
+}
+
+/// A caveat permitting only a single [`Operation`], e.g. "may only
+/// Send".
+pub struct OnlyOperation(pub Operation);
+
+impl<A, M> Guard<A, Operation, CapContext<'_, A, M>> for OnlyOperation {
+    fn attempt(
+        &self,
+        _actor: &A,
+        op: Operation,
+        _ctx: &CapContext<'_, A, M>,
+    ) -> Result<(), AccessDenied> {
+        if op == self.0 {
+            Ok(())
+        } else {
+            Err(AccessDenied)
+        }
+    }
+}
+
+/// A caveat forbidding a single [`Operation`] while permitting
+/// everything else, e.g. "may Receive but not Join".
+pub struct ForbidOperation(pub Operation);
+
+impl<A, M> Guard<A, Operation, CapContext<'_, A, M>> for ForbidOperation {
+    fn attempt(
+        &self,
+        _actor: &A,
+        op: Operation,
+        _ctx: &CapContext<'_, A, M>,
+    ) -> Result<(), AccessDenied> {
+        if op == self.0 {
+            Err(AccessDenied)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A caveat permitting `Send` only when the message matches
+/// `predicate`, e.g. "may only Send messages matching predicate P".
+/// Operations other than `Send` are left alone; a `Send` attempted
+/// through [`Cap::attempt`] without a message (rather than
+/// [`Cap::attempt_send`]) is denied, since there's nothing to check
+/// the predicate against.
+pub struct OnlySendMatching<P>(pub P);
+
+impl<A, M, P> Guard<A, Operation, CapContext<'_, A, M>> for OnlySendMatching<P>
+where
+    P: Fn(&M) -> bool,
+{
+    fn attempt(
+        &self,
+        _actor: &A,
+        op: Operation,
+        ctx: &CapContext<'_, A, M>,
+    ) -> Result<(), AccessDenied> {
+        if op != Operation::Send {
+            return Ok(());
+        }
+        match ctx.message {
+            Some(message) if (self.0)(message) => Ok(()),
+            _ => Err(AccessDenied),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caucus::test_support::{test_caucus, Deny};
+
+    #[test]
+    fn only_operation_narrows_to_a_single_operation() {
+        let caucus = test_caucus::<String>();
+        let cap = Cap::new(&caucus).attenuate(OnlyOperation(Operation::Send));
+        assert!(cap.attempt(&"alice".to_string(), Operation::Send).is_ok());
+        assert!(cap.attempt(&"alice".to_string(), Operation::Join).is_err());
+    }
+
+    #[test]
+    fn forbid_operation_blocks_a_single_operation() {
+        let caucus = test_caucus::<String>();
+        let cap = Cap::new(&caucus).attenuate(ForbidOperation(Operation::Join));
+        assert!(cap.attempt(&"alice".to_string(), Operation::Send).is_ok());
+        assert!(cap.attempt(&"alice".to_string(), Operation::Join).is_err());
+    }
+
+    #[test]
+    fn only_send_matching_checks_the_candidate_message() {
+        let caucus = test_caucus::<String>();
+        let cap = Cap::new(&caucus).attenuate(OnlySendMatching(|m: &String| m == "ok"));
+
+        assert!(cap
+            .attempt_send(&"alice".to_string(), &"ok".to_string())
+            .is_ok());
+        assert!(cap
+            .attempt_send(&"alice".to_string(), &"bad".to_string())
+            .is_err());
+        // Nothing to check the predicate against without a message.
+        assert!(cap.attempt(&"alice".to_string(), Operation::Send).is_err());
+    }
+
+    #[test]
+    fn from_guard_adapts_an_existing_caucus_guard() {
+        let mut caucus = test_caucus::<String>();
+        caucus.send_guard = Box::new(Deny);
+        let cap = Cap::from_guard(&caucus, &caucus.send_guard);
+        assert!(cap.attempt(&"alice".to_string(), Operation::Send).is_err());
+    }
+
+    #[test]
+    fn from_receive_guard_adapts_an_existing_receive_guard() {
+        let mut caucus = test_caucus::<String>();
+        caucus.receive_guard = Box::new(Deny);
+        let cap = Cap::from_receive_guard(&caucus, &caucus.receive_guard);
+        assert!(cap
+            .attempt_receive(&"alice".to_string(), &"hello".to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn attenuation_only_narrows_never_widens() {
+        let caucus = test_caucus::<String>();
+        let cap = Cap::new(&caucus)
+            .attenuate(OnlyOperation(Operation::Send))
+            .attenuate(ForbidOperation(Operation::Send));
+        // The second caveat forbids exactly what the first allows, so
+        // nothing passes both.
+        assert!(cap.attempt(&"alice".to_string(), Operation::Send).is_err());
+        assert!(cap.attempt(&"alice".to_string(), Operation::Join).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An assertion-oriented dataspace for collaborative shared state.
+//!
+//! Where a [`Caucus`](super::Caucus) only delivers transient
+//! messages, a `Dataspace` lets actors publish facts (`assert`) and
+//! watch for facts matching a pattern (`observe`), making it
+//! suitable for presence, document fragments, typing indicators, and
+//! other eventually-consistent shared state.
+
+use std::collections::HashMap;
+
+use crate::access_control::{AccessDenied, Guard};
+
+use super::Transport;
+
+/// Dataspace operations, used for access control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataspaceOp {
+    /// Asserting a fact.
+    Assert,
+    /// Observing the dataspace.
+    Observe,
+}
+
+/// Type alias for the guards on a dataspace.
+pub type DataspaceGuard<A, M> = Box<dyn Guard<A, DataspaceOp, Dataspace<A, M>>>;
+
+/// A handle to a published assertion. Only the actor that produced
+/// it can use this handle to retract it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Assertion(u64);
+
+/// A notification sent to an observer whose pattern matches an
+/// assertion that has appeared or disappeared.
+#[derive(Debug, Clone)]
+pub enum DataspaceEvent<M> {
+    /// A matching assertion was published.
+    Added(M),
+    /// A previously-matching assertion was retracted.
+    Removed(M),
+}
+
+struct Published<A, M> {
+    publisher: A,
+    value: M,
+}
+
+struct Observer<A, M> {
+    actor: A,
+    pattern: Box<dyn Fn(&M) -> bool>,
+}
+
+/// A shared space of facts that actors assert, retract, and observe.
+///
+/// Type Parameters:
+///
+/// * `A`: The type of actor participating in the dataspace.
+/// * `M`: The type of value asserted into the dataspace.
+pub struct Dataspace<A, M> {
+    /// Control who can assert into the dataspace.
+    pub assert_guard: DataspaceGuard<A, M>,
+    /// Control who can observe the dataspace.
+    pub observe_guard: DataspaceGuard<A, M>,
+    /// How `Added`/`Removed` notifications are delivered to
+    /// observers.
+    pub transport: Box<dyn Transport<A, DataspaceEvent<M>>>,
+    assertions: HashMap<Assertion, Published<A, M>>,
+    observers: Vec<Observer<A, M>>,
+    next_handle: u64,
+}
+
+impl<A, M> Dataspace<A, M> {
+    /// Create an empty dataspace gated by `assert_guard` and
+    /// `observe_guard`, delivering notifications via `transport`.
+    pub fn new(
+        assert_guard: DataspaceGuard<A, M>,
+        observe_guard: DataspaceGuard<A, M>,
+        transport: Box<dyn Transport<A, DataspaceEvent<M>>>,
+    ) -> Self {
+        Dataspace {
+            assert_guard,
+            observe_guard,
+            transport,
+            assertions: HashMap::new(),
+            observers: Vec::new(),
+            next_handle: 0,
+        }
+    }
+}
+
+impl<A: PartialEq, M: Clone> Dataspace<A, M> {
+    /// Publish `value` as a fact on behalf of `publisher`, notifying
+    /// every observer whose pattern matches it. Returns a handle that
+    /// only `publisher` may later use to retract it.
+    pub fn assert(&mut self, publisher: A, value: M) -> Result<Assertion, AccessDenied> {
+        self.assert_guard
+            .attempt(&publisher, DataspaceOp::Assert, self)?;
+
+        for observer in &self.observers {
+            if (observer.pattern)(&value) {
+                let _ = self
+                    .transport
+                    .deliver(&observer.actor, &DataspaceEvent::Added(value.clone()));
+            }
+        }
+
+        let handle = Assertion(self.next_handle);
+        self.next_handle += 1;
+        self.assertions
+            .insert(handle, Published { publisher, value });
+        Ok(handle)
+    }
+
+    /// Retract `handle` on behalf of `actor`, notifying every
+    /// observer whose pattern matched the retracted value. Retracting
+    /// an already-gone handle is a no-op; retracting someone else's
+    /// assertion is denied.
+    pub fn retract(&mut self, actor: &A, handle: Assertion) -> Result<(), AccessDenied> {
+        match self.assertions.get(&handle) {
+            None => return Ok(()),
+            Some(published) if &published.publisher != actor => return Err(AccessDenied),
+            Some(_) => {}
+        }
+
+        let published = self
+            .assertions
+            .remove(&handle)
+            .expect("presence just confirmed above");
+        for observer in &self.observers {
+            if (observer.pattern)(&published.value) {
+                let _ = self.transport.deliver(
+                    &observer.actor,
+                    &DataspaceEvent::Removed(published.value.clone()),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Register `actor`'s interest in assertions matching `pattern`.
+    /// Assertions already live and matching are immediately announced
+    /// as `Added` events.
+    pub fn observe(
+        &mut self,
+        actor: A,
+        pattern: Box<dyn Fn(&M) -> bool>,
+    ) -> Result<(), AccessDenied>
+    where
+        A: Clone,
+    {
+        self.observe_guard
+            .attempt(&actor, DataspaceOp::Observe, self)?;
+
+        for published in self.assertions.values() {
+            if pattern(&published.value) {
+                let _ = self
+                    .transport
+                    .deliver(&actor, &DataspaceEvent::Added(published.value.clone()));
+            }
+        }
+
+        self.observers.push(Observer { actor, pattern });
+        Ok(())
+    }
+
+    /// Remove `actor` from the dataspace: retract every assertion it
+    /// published and drop its observer registration, if any.
+    pub fn remove_actor(&mut self, actor: &A) {
+        let handles: Vec<Assertion> = self
+            .assertions
+            .iter()
+            .filter(|(_, published)| &published.publisher == actor)
+            .map(|(handle, _)| *handle)
+            .collect();
+        for handle in handles {
+            let _ = self.retract(actor, handle);
+        }
+        self.observers.retain(|observer| &observer.actor != actor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caucus::test_support::{test_dataspace, Allow};
+    use crate::caucus::ChannelTransport;
+
+    #[test]
+    fn observer_is_notified_of_matching_assertions() {
+        let mut transport = ChannelTransport::<String, DataspaceEvent<String>>::new();
+        let alice = transport.register("alice".to_string());
+        let mut ds = Dataspace::new(Box::new(Allow), Box::new(Allow), Box::new(transport));
+
+        ds.observe("alice".to_string(), Box::new(|v: &String| v == "hello"))
+            .unwrap();
+        ds.assert("bob".to_string(), "nope".to_string()).unwrap();
+        ds.assert("bob".to_string(), "hello".to_string()).unwrap();
+
+        match alice.try_recv() {
+            Some(DataspaceEvent::Added(value)) => assert_eq!(value, "hello"),
+            other => panic!("expected a matching Added event, got {other:?}"),
+        }
+        assert!(alice.try_recv().is_none());
+    }
+
+    #[test]
+    fn observing_an_existing_assertion_announces_it_immediately() {
+        let mut transport = ChannelTransport::<String, DataspaceEvent<String>>::new();
+        let alice = transport.register("alice".to_string());
+        let mut ds = Dataspace::new(Box::new(Allow), Box::new(Allow), Box::new(transport));
+
+        ds.assert("bob".to_string(), "hello".to_string()).unwrap();
+        ds.observe("alice".to_string(), Box::new(|_: &String| true))
+            .unwrap();
+
+        match alice.try_recv() {
+            Some(DataspaceEvent::Added(value)) => assert_eq!(value, "hello"),
+            other => {
+                panic!("expected an Added event for the pre-existing assertion, got {other:?}")
+            }
+        }
+    }
+
+    #[test]
+    fn retract_is_denied_for_a_non_publisher_and_a_no_op_when_already_gone() {
+        let mut ds = test_dataspace();
+        let handle = ds.assert("alice".to_string(), "x".to_string()).unwrap();
+
+        assert!(ds.retract(&"bob".to_string(), handle).is_err());
+        assert!(ds.retract(&"alice".to_string(), handle).is_ok());
+        assert!(ds.retract(&"alice".to_string(), handle).is_ok());
+    }
+
+    #[test]
+    fn remove_actor_retracts_its_assertions_and_drops_its_observer() {
+        let mut ds = test_dataspace();
+        ds.assert("alice".to_string(), "x".to_string()).unwrap();
+        ds.observe("alice".to_string(), Box::new(|_: &String| true))
+            .unwrap();
+
+        ds.remove_actor(&"alice".to_string());
+
+        assert!(ds.assertions.is_empty());
+        assert!(ds.observers.is_empty());
+    }
+}
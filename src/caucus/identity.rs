@@ -0,0 +1,230 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Cryptographic identities and authenticated messages.
+//!
+//! An [`Identity`] pairs a public key with the secret it signs
+//! messages with, plus a local [`Petname`] table: human-chosen names
+//! for other actors that are bookkeeping for this identity's own
+//! holder only, and never transmitted or consulted by
+//! `Caucus::broadcast` or any guard. Messages sent through a caucus
+//! are wrapped in a [`Signed`] envelope authenticated with
+//! HMAC-SHA256 over `(sender, sequence, message)`. Verifying a
+//! `Signed` envelope requires the secret registered for the claimed
+//! sender specifically (see `Caucus::register_key`) — not some
+//! caucus-wide shared value — since a tag checked against the wrong
+//! secret proves nothing about who actually produced the message.
+
+use std::collections::HashMap;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A human-chosen local name for another actor.
+///
+/// Petnames are local to the [`Identity`] that holds them and are
+/// never transmitted over the wire.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Petname(pub String);
+
+/// An actor's public key, used to verify messages it has signed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PublicKey(pub [u8; 32]);
+
+/// An error verifying a [`Signed`] message.
+#[derive(Debug)]
+pub enum AuthError {
+    /// The HMAC tag did not match the message.
+    BadTag,
+    /// The sequence number was not newer than the last one seen from
+    /// this sender, i.e. this looks like a replay.
+    Replay,
+    /// The claimed sender's public key isn't known to the caucus, so
+    /// there's no actor to deliver the message as, and no secret to
+    /// verify it against.
+    UnknownSender,
+}
+
+/// A message wrapped with its sender's public key, a sequence number,
+/// and an HMAC-SHA256 tag authenticating `(sender, sequence, message)`.
+#[derive(Debug, Clone)]
+pub struct Signed<M> {
+    /// The public key of the actor that produced this message.
+    pub sender: PublicKey,
+    /// A monotonic per-sender counter, guarding against replay.
+    pub sequence: u64,
+    /// The wrapped message.
+    pub message: M,
+    /// The HMAC-SHA256 tag over `(sender, sequence, message)`.
+    pub tag: Vec<u8>,
+}
+
+/// An actor's cryptographic identity: its public key and the secret
+/// it authenticates its own messages with.
+///
+/// Each actor is expected to hold its own secret, distinct from every
+/// other actor's. A caucus verifies an incoming message against the
+/// secret registered for that message's claimed sender, so forging a
+/// tag for another actor's key requires that actor's secret, not just
+/// any caucus member's.
+pub struct Identity {
+    public_key: PublicKey,
+    secret: Vec<u8>,
+    sequence: u64,
+    petnames: HashMap<Petname, PublicKey>,
+}
+
+impl Identity {
+    /// Create an identity for `public_key`, authenticating with
+    /// `secret`.
+    pub fn new(public_key: PublicKey, secret: Vec<u8>) -> Self {
+        Identity {
+            public_key,
+            secret,
+            sequence: 0,
+            petnames: HashMap::new(),
+        }
+    }
+
+    /// This identity's public key.
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// This identity's secret, e.g. to hand to
+    /// `Caucus::register_key` alongside the public key.
+    pub fn secret(&self) -> &[u8] {
+        &self.secret
+    }
+
+    /// Give `actor` the local name `name`, for this identity's own
+    /// bookkeeping. Not consulted by `Caucus::broadcast` or any
+    /// guard, and never transmitted.
+    pub fn name(&mut self, name: Petname, actor: PublicKey) {
+        self.petnames.insert(name, actor);
+    }
+
+    /// Look up the public key behind a locally-assigned petname.
+    pub fn resolve(&self, name: &Petname) -> Option<&PublicKey> {
+        self.petnames.get(name)
+    }
+
+    /// Sign `message`, advancing this identity's sequence number so
+    /// the resulting envelope cannot be replayed.
+    pub fn sign<M: AsRef<[u8]>>(&mut self, message: M) -> Signed<M> {
+        self.sequence += 1;
+        let tag = Self::tag(
+            &self.secret,
+            &self.public_key,
+            self.sequence,
+            message.as_ref(),
+        );
+        Signed {
+            sender: self.public_key.clone(),
+            sequence: self.sequence,
+            message,
+            tag,
+        }
+    }
+
+    /// Verify that `signed` was authenticated with `secret` — which
+    /// must be the secret registered for `signed.sender`, not an
+    /// arbitrary or shared one — and that its sequence number is
+    /// newer than `last_seen`.
+    pub fn verify_with_secret<M: AsRef<[u8]>>(
+        secret: &[u8],
+        signed: &Signed<M>,
+        last_seen: u64,
+    ) -> Result<(), AuthError> {
+        if signed.sequence <= last_seen {
+            return Err(AuthError::Replay);
+        }
+        let expected = Self::tag(
+            secret,
+            &signed.sender,
+            signed.sequence,
+            signed.message.as_ref(),
+        );
+        if expected == signed.tag {
+            Ok(())
+        } else {
+            Err(AuthError::BadTag)
+        }
+    }
+
+    fn tag(secret: &[u8], sender: &PublicKey, sequence: u64, message: &[u8]) -> Vec<u8> {
+        let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+        mac.update(&sender.0);
+        mac.update(&sequence.to_be_bytes());
+        mac.update(message);
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_distinct_senders_each_verify_against_their_own_secret() {
+        let mut alice = Identity::new(PublicKey([1; 32]), b"alice's secret".to_vec());
+        let mut bob = Identity::new(PublicKey([2; 32]), b"bob's secret".to_vec());
+
+        let from_alice = alice.sign(b"hello".to_vec());
+        let from_bob = bob.sign(b"hi".to_vec());
+
+        assert!(Identity::verify_with_secret(b"alice's secret", &from_alice, 0).is_ok());
+        assert!(Identity::verify_with_secret(b"bob's secret", &from_bob, 0).is_ok());
+    }
+
+    #[test]
+    fn alices_secret_does_not_verify_a_message_claiming_to_be_bobs() {
+        let mut alice = Identity::new(PublicKey([1; 32]), b"alice's secret".to_vec());
+        let mut forged = alice.sign(b"hello".to_vec());
+        forged.sender = PublicKey([2; 32]);
+
+        assert!(matches!(
+            Identity::verify_with_secret(b"bob's secret", &forged, 0),
+            Err(AuthError::BadTag)
+        ));
+    }
+
+    #[test]
+    fn replayed_sequence_is_rejected() {
+        let mut alice = Identity::new(PublicKey([1; 32]), b"alice's secret".to_vec());
+        let signed = alice.sign(b"hello".to_vec());
+
+        assert!(matches!(
+            Identity::verify_with_secret(b"alice's secret", &signed, signed.sequence),
+            Err(AuthError::Replay)
+        ));
+    }
+
+    #[test]
+    fn wrong_secret_fails_the_tag() {
+        let mut alice = Identity::new(PublicKey([1; 32]), b"alice's secret".to_vec());
+        let signed = alice.sign(b"hello".to_vec());
+
+        assert!(matches!(
+            Identity::verify_with_secret(b"a different secret", &signed, 0),
+            Err(AuthError::BadTag)
+        ));
+    }
+
+    #[test]
+    fn petnames_resolve_to_the_named_key() {
+        let mut alice = Identity::new(PublicKey([1; 32]), b"secret".to_vec());
+        alice.name(Petname("bob".to_string()), PublicKey([2; 32]));
+
+        assert_eq!(
+            alice.resolve(&Petname("bob".to_string())),
+            Some(&PublicKey([2; 32]))
+        );
+        assert_eq!(alice.resolve(&Petname("carol".to_string())), None);
+    }
+}
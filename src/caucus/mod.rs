@@ -11,9 +11,50 @@
 //! list, a discussion around an article, collaboration on a
 //! document, etc.
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+
 use crate::access_control::{AccessDenied, Guard};
 
+mod capability;
+mod dataspace;
+mod identity;
+mod payload;
+mod rate_limit;
+#[cfg(test)]
+mod test_support;
+mod transport;
+
+pub use self::capability::{Cap, CapContext, ForbidOperation, OnlyOperation, OnlySendMatching};
+pub use self::dataspace::{Assertion, Dataspace, DataspaceEvent, DataspaceGuard, DataspaceOp};
+pub use self::identity::{AuthError, Identity, Petname, PublicKey, Signed};
+pub use self::payload::{IgnorePayload, OnlyMentioned, Payload, ReceiveContext, ReceiveGuard};
+pub use self::rate_limit::{AndGuard, RateLimit, RateLimitGuard, RateLimited};
+pub use self::transport::{ChannelTransport, DeliveryError, EventStream, Transport};
+
+/// An error returned by [`Caucus::broadcast`].
+#[derive(Debug)]
+pub enum BroadcastError {
+    /// Access control denied the operation.
+    Access(AccessDenied),
+    /// The message failed signature verification.
+    Auth(AuthError),
+}
+
+impl From<AccessDenied> for BroadcastError {
+    fn from(error: AccessDenied) -> Self {
+        BroadcastError::Access(error)
+    }
+}
+
+impl From<AuthError> for BroadcastError {
+    fn from(error: AuthError) -> Self {
+        BroadcastError::Auth(error)
+    }
+}
+
 /// Caucus operations, used for access control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Operation {
     /// Joining a caucus.
     Join,
@@ -40,10 +81,25 @@ pub struct Caucus<A, M> {
     pub join_guard: CaucusGuard<A, M>,
     /// Control who can send to the caucus.
     pub send_guard: CaucusGuard<A, M>,
-    /// Control who will receive messages from the caucus.
-    pub receive_guard: CaucusGuard<A, M>,
+    /// Control who will receive messages from the caucus, consulted
+    /// with the in-flight message attached via `ReceiveContext`.
+    pub receive_guard: ReceiveGuard<A, M>,
     /// Actors in the caucus.
     pub actors: Vec<A>,
+    /// How messages are actually delivered to actors.
+    pub transport: Box<dyn Transport<A, M>>,
+    /// The last accepted sequence number seen from each verified
+    /// sender key, used to reject replayed messages.
+    pub last_sequence: RefCell<HashMap<PublicKey, u64>>,
+    /// The actor and authenticating secret behind each known public
+    /// key. `broadcast` resolves a `Signed` message's sender through
+    /// this table rather than trusting a caller-supplied actor
+    /// handle, and verifies its tag against that sender's own secret
+    /// specifically rather than any single caucus-wide value — so
+    /// guards are always consulted with the actually-authenticated
+    /// actor, and forging a tag for one actor's key requires that
+    /// actor's own secret.
+    pub known_keys: RefCell<HashMap<PublicKey, (A, Vec<u8>)>>,
 }
 
 impl<A: PartialEq, M> Caucus<A, M> {
@@ -63,9 +119,181 @@ impl<A: PartialEq, M> Caucus<A, M> {
         Ok(())
     }
 
-    /// Broadcast a message to the actors in the caucus.
-    pub fn broadcast(&self, _sender: &A, _message: &M) -> Result<(), AccessDenied> {
-        // How do we actually send a message to the actors?
+    /// Record that `key` belongs to `actor` and is authenticated with
+    /// `secret`, so a [`Signed`] message claiming to be from `key` can
+    /// be resolved to `actor` and verified against `secret` by
+    /// `broadcast`. `secret` should be `actor`'s own
+    /// [`Identity::secret`], not shared with any other actor.
+    pub fn register_key(&self, actor: A, key: PublicKey, secret: Vec<u8>) {
+        self.known_keys.borrow_mut().insert(key, (actor, secret));
+    }
+}
+
+impl<A: Clone, M: AsRef<[u8]>> Caucus<A, M> {
+    /// Broadcast a signed message to the actors in the caucus.
+    ///
+    /// The sender is resolved from `message.sender` via `known_keys`
+    /// first — not taken on trust from the caller — and the message
+    /// verified against that specific sender's registered secret, so
+    /// a bad tag, an unknown sender, or a replayed sequence number
+    /// never reaches `send_guard`/`receive_guard`. `send_guard` is
+    /// then checked against the resolved actor, and only once it
+    /// accepts does `last_sequence` advance: an unauthorized sender
+    /// could otherwise plant an arbitrary sequence number for a key
+    /// it doesn't own and permanently lock out that key's real holder
+    /// by making every subsequent genuine message look like a replay.
+    /// Finally each actor is consulted via `receive_guard` before the
+    /// message is handed to the `transport`; actors the guard denies
+    /// simply don't receive the message. Delivery failures (e.g. an
+    /// actor that has gone away) are not treated as errors.
+    pub fn broadcast(&self, message: &Signed<M>) -> Result<(), BroadcastError> {
+        let (sender, secret) = self
+            .known_keys
+            .borrow()
+            .get(&message.sender)
+            .cloned()
+            .ok_or(AuthError::UnknownSender)?;
+
+        let last_seen = *self
+            .last_sequence
+            .borrow()
+            .get(&message.sender)
+            .unwrap_or(&0);
+        Identity::verify_with_secret(&secret, message, last_seen)?;
+
+        self.send_guard.attempt(&sender, Operation::Send, self)?;
+
+        self.last_sequence
+            .borrow_mut()
+            .insert(message.sender.clone(), message.sequence);
+
+        let ctx = ReceiveContext {
+            caucus: self,
+            message: &message.message,
+        };
+        for actor in &self.actors {
+            if self
+                .receive_guard
+                .attempt(actor, Operation::Receive, &ctx)
+                .is_ok()
+            {
+                let _ = self.transport.deliver(actor, &message.message);
+            }
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caucus::test_support::{Allow, Deny};
+    use crate::caucus::ChannelTransport;
+
+    fn caucus_with_guards(
+        send_guard: CaucusGuard<String, String>,
+        receive_guard: ReceiveGuard<String, String>,
+    ) -> (
+        Caucus<String, String>,
+        EventStream<String>,
+        EventStream<String>,
+    ) {
+        let mut transport = ChannelTransport::new();
+        let alice_stream = transport.register("alice".to_string());
+        let bob_stream = transport.register("bob".to_string());
+
+        let caucus = Caucus {
+            join_guard: Box::new(Allow),
+            send_guard,
+            receive_guard,
+            actors: vec!["alice".to_string(), "bob".to_string()],
+            transport: Box::new(transport),
+            last_sequence: RefCell::new(HashMap::new()),
+            known_keys: RefCell::new(HashMap::new()),
+        };
+        (caucus, alice_stream, bob_stream)
+    }
+
+    #[test]
+    fn broadcast_delivers_to_every_actor() {
+        let (caucus, alice_stream, bob_stream) =
+            caucus_with_guards(Box::new(Allow), Box::new(Allow));
+        caucus.register_key(
+            "alice".to_string(),
+            PublicKey([1; 32]),
+            b"alice's secret".to_vec(),
+        );
+        let mut sender = Identity::new(PublicKey([1; 32]), b"alice's secret".to_vec());
+        let signed = sender.sign("hello".to_string());
+
+        assert!(caucus.broadcast(&signed).is_ok());
+        assert_eq!(alice_stream.try_recv(), Some("hello".to_string()));
+        assert_eq!(bob_stream.try_recv(), Some("hello".to_string()));
+    }
+
+    #[test]
+    fn broadcast_rejects_an_unregistered_sender() {
+        let (caucus, _alice_stream, _bob_stream) =
+            caucus_with_guards(Box::new(Allow), Box::new(Allow));
+        let mut sender = Identity::new(PublicKey([9; 32]), b"nobody's secret".to_vec());
+        let signed = sender.sign("hello".to_string());
+
+        assert!(matches!(
+            caucus.broadcast(&signed),
+            Err(BroadcastError::Auth(AuthError::UnknownSender))
+        ));
+    }
+
+    #[test]
+    fn broadcast_is_denied_when_send_guard_forbids_the_sender() {
+        let (caucus, alice_stream, bob_stream) =
+            caucus_with_guards(Box::new(Deny), Box::new(Allow));
+        caucus.register_key(
+            "alice".to_string(),
+            PublicKey([1; 32]),
+            b"alice's secret".to_vec(),
+        );
+        let mut sender = Identity::new(PublicKey([1; 32]), b"alice's secret".to_vec());
+        let signed = sender.sign("hello".to_string());
+
+        assert!(matches!(
+            caucus.broadcast(&signed),
+            Err(BroadcastError::Access(_))
+        ));
+        assert!(alice_stream.try_recv().is_none());
+        assert!(bob_stream.try_recv().is_none());
+    }
+
+    #[test]
+    fn receive_guard_can_withhold_delivery_from_specific_actors() {
+        struct OnlyBob;
+        impl Guard<String, Operation, ReceiveContext<'_, String, String>> for OnlyBob {
+            fn attempt(
+                &self,
+                actor: &String,
+                _op: Operation,
+                _ctx: &ReceiveContext<'_, String, String>,
+            ) -> Result<(), AccessDenied> {
+                if actor == "bob" {
+                    Ok(())
+                } else {
+                    Err(AccessDenied)
+                }
+            }
+        }
+
+        let (caucus, alice_stream, bob_stream) =
+            caucus_with_guards(Box::new(Allow), Box::new(OnlyBob));
+        caucus.register_key(
+            "alice".to_string(),
+            PublicKey([1; 32]),
+            b"alice's secret".to_vec(),
+        );
+        let mut sender = Identity::new(PublicKey([1; 32]), b"alice's secret".to_vec());
+        let signed = sender.sign("hello".to_string());
+
+        assert!(caucus.broadcast(&signed).is_ok());
+        assert!(alice_stream.try_recv().is_none());
+        assert_eq!(bob_stream.try_recv(), Some("hello".to_string()));
+    }
+}
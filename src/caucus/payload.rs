@@ -0,0 +1,128 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Content-aware receive filtering.
+//!
+//! Message types can implement [`Payload`] to describe who and what
+//! they concern. `Caucus::broadcast` passes that information to
+//! `receive_guard` via [`ReceiveContext`], so a guard can decide
+//! "deliver only to mentioned actors" or "only to actors subscribed
+//! to this topic" instead of just fanning a message out blindly.
+
+use crate::access_control::{AccessDenied, Guard};
+
+use super::{Caucus, CaucusGuard, Operation};
+
+/// A message that can describe who and what it concerns.
+pub trait Payload<A> {
+    /// The actors this message specifically concerns, e.g. mentions.
+    fn involved_actors(&self) -> &[A];
+
+    /// The language the message is written in, if known.
+    fn language(&self) -> Option<&str> {
+        None
+    }
+
+    /// The topic the message belongs to, if any.
+    fn topic(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// What a `receive_guard` is consulted with: the caucus itself, plus
+/// the message about to be delivered.
+pub struct ReceiveContext<'a, A, M> {
+    /// The caucus the message is being broadcast through.
+    pub caucus: &'a Caucus<A, M>,
+    /// The message about to be delivered.
+    pub message: &'a M,
+}
+
+/// The type of guard used for `Caucus::receive_guard`: like
+/// [`CaucusGuard`], but consulted with the in-flight message attached
+/// via [`ReceiveContext`].
+pub type ReceiveGuard<A, M> = Box<dyn for<'a> Guard<A, Operation, ReceiveContext<'a, A, M>>>;
+
+/// Adapts an existing [`CaucusGuard`] into a `receive_guard`, for
+/// guards that don't need to look at the message content.
+pub struct IgnorePayload<A, M>(pub CaucusGuard<A, M>);
+
+impl<A, M> Guard<A, Operation, ReceiveContext<'_, A, M>> for IgnorePayload<A, M> {
+    fn attempt(
+        &self,
+        actor: &A,
+        op: Operation,
+        ctx: &ReceiveContext<'_, A, M>,
+    ) -> Result<(), AccessDenied> {
+        self.0.attempt(actor, op, ctx.caucus)
+    }
+}
+
+/// A guard that only delivers a message to the actors named in its
+/// [`Payload::involved_actors`].
+pub struct OnlyMentioned;
+
+impl<A: PartialEq, M: Payload<A>> Guard<A, Operation, ReceiveContext<'_, A, M>> for OnlyMentioned {
+    fn attempt(
+        &self,
+        actor: &A,
+        _op: Operation,
+        ctx: &ReceiveContext<'_, A, M>,
+    ) -> Result<(), AccessDenied> {
+        if ctx.message.involved_actors().iter().any(|a| a == actor) {
+            Ok(())
+        } else {
+            Err(AccessDenied)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caucus::test_support::{test_caucus, Deny};
+
+    #[derive(Clone)]
+    struct Mention(Vec<String>);
+
+    impl Payload<String> for Mention {
+        fn involved_actors(&self) -> &[String] {
+            &self.0
+        }
+    }
+
+    #[test]
+    fn only_mentioned_permits_named_actors_and_denies_others() {
+        let caucus = test_caucus::<Mention>();
+        let message = Mention(vec!["alice".to_string()]);
+        let ctx = ReceiveContext {
+            caucus: &caucus,
+            message: &message,
+        };
+
+        assert!(OnlyMentioned
+            .attempt(&"alice".to_string(), Operation::Receive, &ctx)
+            .is_ok());
+        assert!(OnlyMentioned
+            .attempt(&"bob".to_string(), Operation::Receive, &ctx)
+            .is_err());
+    }
+
+    #[test]
+    fn ignore_payload_adapts_an_existing_caucus_guard() {
+        let caucus = test_caucus::<Mention>();
+        let message = Mention(vec![]);
+        let ctx = ReceiveContext {
+            caucus: &caucus,
+            message: &message,
+        };
+
+        let adapted = IgnorePayload(Box::new(Deny) as CaucusGuard<String, Mention>);
+        assert!(adapted
+            .attempt(&"anyone".to_string(), Operation::Receive, &ctx)
+            .is_err());
+    }
+}
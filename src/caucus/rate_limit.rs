@@ -0,0 +1,304 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Token-bucket rate limiting for caucus operations.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::access_control::{AccessDenied, Guard};
+
+use super::{Caucus, Operation};
+
+/// A per-actor, per-operation token bucket: holds up to `capacity`
+/// tokens, refilling at `refill_rate` tokens per second.
+struct Bucket {
+    capacity: f64,
+    refill_rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn new(capacity: f64, refill_rate: f64) -> Self {
+        Bucket {
+            capacity,
+            refill_rate,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Refill based on elapsed time, then try to take one token.
+    /// Returns how long to wait before a token will next be
+    /// available if none was taken. A non-positive `refill_rate`
+    /// means the bucket never refills (a hard cap): it never divides
+    /// by zero, and an empty bucket reports `Duration::MAX` rather
+    /// than a meaningless wait.
+    fn take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        if self.refill_rate > 0.0 {
+            let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        }
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else if self.refill_rate > 0.0 {
+            let deficit = 1.0 - self.tokens;
+            Err(Duration::from_secs_f64(deficit / self.refill_rate))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+}
+
+/// The capacity and refill rate of a [`RateLimitGuard`]'s bucket for
+/// one operation.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// The maximum number of tokens a bucket can hold.
+    pub capacity: f64,
+    /// How many tokens are added back per second.
+    pub refill_rate: f64,
+}
+
+/// The bucket for an attempted operation was empty.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimited {
+    /// How long the caller should wait before retrying.
+    pub retry_after: Duration,
+}
+
+/// A [`Guard`] that throttles each actor with a token bucket per
+/// [`Operation`], so e.g. `Send` can be limited more tightly than
+/// `Receive`. Operations with no configured limit are let through
+/// unthrottled.
+pub struct RateLimitGuard<A> {
+    limits: HashMap<Operation, RateLimit>,
+    buckets: RefCell<HashMap<(A, Operation), Bucket>>,
+    /// The reason the most recent attempt through the `Guard` impl's
+    /// `attempt` was denied (cleared when a later attempt succeeds),
+    /// keyed by `(actor, operation)` since
+    /// `AccessDenied` itself carries no payload and a single call can
+    /// be interleaved with other actors'/operations' attempts (e.g.
+    /// `Caucus::broadcast` consulting `receive_guard` once per
+    /// actor). See [`RateLimitGuard::last_denial`].
+    last_denial: RefCell<HashMap<(A, Operation), RateLimited>>,
+}
+
+impl<A: Eq + Hash + Clone> RateLimitGuard<A> {
+    /// Create a guard with no configured limits.
+    pub fn new() -> Self {
+        RateLimitGuard {
+            limits: HashMap::new(),
+            buckets: RefCell::new(HashMap::new()),
+            last_denial: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Configure the bucket used for `operation`.
+    pub fn limit(mut self, operation: Operation, limit: RateLimit) -> Self {
+        self.limits.insert(operation, limit);
+        self
+    }
+
+    /// Attempt to consume a token for `actor` performing `operation`,
+    /// returning how long to wait if the bucket is currently empty.
+    pub fn check(&self, actor: &A, operation: Operation) -> Result<(), RateLimited> {
+        let limit = match self.limits.get(&operation) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let mut buckets = self.buckets.borrow_mut();
+        let bucket = buckets
+            .entry((actor.clone(), operation))
+            .or_insert_with(|| Bucket::new(limit.capacity, limit.refill_rate));
+        bucket
+            .take()
+            .map_err(|retry_after| RateLimited { retry_after })
+    }
+
+    /// The reason `actor` performing `operation` was denied, if its
+    /// most recent attempt through the `Guard` impl's `attempt` was
+    /// denied; a later successful attempt clears it. `AccessDenied` carries no payload, so a caller going
+    /// through the `Guard` trait (rather than calling `check`
+    /// directly) recovers the retry-after duration this way. Keyed
+    /// per `(actor, operation)` so one actor's denial survives being
+    /// interleaved with other actors'/operations' attempts in the
+    /// same `Guard` chain.
+    pub fn last_denial(&self, actor: &A, operation: Operation) -> Option<RateLimited> {
+        self.last_denial
+            .borrow()
+            .get(&(actor.clone(), operation))
+            .copied()
+    }
+}
+
+impl<A: Eq + Hash + Clone> Default for RateLimitGuard<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Eq + Hash + Clone, M> Guard<A, Operation, Caucus<A, M>> for RateLimitGuard<A> {
+    fn attempt(&self, actor: &A, op: Operation, _ctx: &Caucus<A, M>) -> Result<(), AccessDenied> {
+        let key = (actor.clone(), op);
+        match self.check(actor, op) {
+            Ok(()) => {
+                self.last_denial.borrow_mut().remove(&key);
+                Ok(())
+            }
+            Err(denial) => {
+                self.last_denial.borrow_mut().insert(key, denial);
+                Err(AccessDenied)
+            }
+        }
+    }
+}
+
+/// A [`Guard`] combinator requiring every guard in the chain to
+/// permit the operation, short-circuiting on the first denial. This
+/// is how a [`RateLimitGuard`] gets stacked on top of an existing
+/// authorization guard.
+pub struct AndGuard<A, Op, Ctx> {
+    guards: Vec<Box<dyn Guard<A, Op, Ctx>>>,
+}
+
+impl<A, Op, Ctx> AndGuard<A, Op, Ctx> {
+    /// Combine `guards` into one, requiring all of them to pass.
+    pub fn new(guards: Vec<Box<dyn Guard<A, Op, Ctx>>>) -> Self {
+        AndGuard { guards }
+    }
+}
+
+impl<A, Op: Copy, Ctx> Guard<A, Op, Ctx> for AndGuard<A, Op, Ctx> {
+    fn attempt(&self, actor: &A, op: Op, ctx: &Ctx) -> Result<(), AccessDenied> {
+        for guard in &self.guards {
+            guard.attempt(actor, op, ctx)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caucus::test_support::test_caucus;
+
+    #[test]
+    fn unconfigured_operations_are_unthrottled() {
+        let guard: RateLimitGuard<String> = RateLimitGuard::new();
+        assert!(guard.check(&"alice".to_string(), Operation::Send).is_ok());
+        assert!(guard.check(&"alice".to_string(), Operation::Send).is_ok());
+    }
+
+    #[test]
+    fn exhausted_bucket_reports_a_retry_after() {
+        let guard = RateLimitGuard::new().limit(
+            Operation::Send,
+            RateLimit {
+                capacity: 1.0,
+                refill_rate: 1.0,
+            },
+        );
+        assert!(guard.check(&"alice".to_string(), Operation::Send).is_ok());
+        assert!(guard.check(&"alice".to_string(), Operation::Send).is_err());
+    }
+
+    #[test]
+    fn zero_refill_rate_never_panics_and_never_refills() {
+        let guard = RateLimitGuard::new().limit(
+            Operation::Send,
+            RateLimit {
+                capacity: 1.0,
+                refill_rate: 0.0,
+            },
+        );
+        assert!(guard.check(&"alice".to_string(), Operation::Send).is_ok());
+        match guard.check(&"alice".to_string(), Operation::Send) {
+            Err(RateLimited { retry_after }) => assert_eq!(retry_after, Duration::MAX),
+            Ok(()) => panic!("bucket should be empty"),
+        }
+    }
+
+    #[test]
+    fn guard_impl_surfaces_retry_after_via_last_denial() {
+        let guard = RateLimitGuard::new().limit(
+            Operation::Send,
+            RateLimit {
+                capacity: 1.0,
+                refill_rate: 1.0,
+            },
+        );
+        let caucus = test_caucus::<String>();
+
+        assert!(guard
+            .attempt(&"alice".to_string(), Operation::Send, &caucus)
+            .is_ok());
+        assert!(guard
+            .last_denial(&"alice".to_string(), Operation::Send)
+            .is_none());
+
+        assert!(guard
+            .attempt(&"alice".to_string(), Operation::Send, &caucus)
+            .is_err());
+        assert!(guard
+            .last_denial(&"alice".to_string(), Operation::Send)
+            .is_some());
+    }
+
+    #[test]
+    fn last_denial_survives_being_interleaved_with_other_actors_and_operations() {
+        let guard = RateLimitGuard::new()
+            .limit(
+                Operation::Send,
+                RateLimit {
+                    capacity: 1.0,
+                    refill_rate: 1.0,
+                },
+            )
+            .limit(
+                Operation::Join,
+                RateLimit {
+                    capacity: 1.0,
+                    refill_rate: 1.0,
+                },
+            );
+        let caucus = test_caucus::<String>();
+
+        // Exhaust alice's Send bucket.
+        assert!(guard
+            .attempt(&"alice".to_string(), Operation::Send, &caucus)
+            .is_ok());
+        assert!(guard
+            .attempt(&"alice".to_string(), Operation::Send, &caucus)
+            .is_err());
+
+        // bob's successful Join, and alice's own successful Join,
+        // must not clobber alice's recorded Send denial.
+        assert!(guard
+            .attempt(&"bob".to_string(), Operation::Join, &caucus)
+            .is_ok());
+        assert!(guard
+            .attempt(&"alice".to_string(), Operation::Join, &caucus)
+            .is_ok());
+
+        assert!(guard
+            .last_denial(&"alice".to_string(), Operation::Send)
+            .is_some());
+        assert!(guard
+            .last_denial(&"bob".to_string(), Operation::Join)
+            .is_none());
+        assert!(guard
+            .last_denial(&"alice".to_string(), Operation::Join)
+            .is_none());
+    }
+}
@@ -0,0 +1,60 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Shared fixtures for the unit tests in `caucus`'s submodules, so
+//! each one isn't re-declaring the same trivial guards and test
+//! rigging.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::access_control::{AccessDenied, Guard};
+
+use super::{Caucus, ChannelTransport, Dataspace};
+
+/// A guard that permits every operation, regardless of context.
+pub(crate) struct Allow;
+
+impl<A, Op, Ctx> Guard<A, Op, Ctx> for Allow {
+    fn attempt(&self, _actor: &A, _op: Op, _ctx: &Ctx) -> Result<(), AccessDenied> {
+        Ok(())
+    }
+}
+
+/// A guard that denies every operation, regardless of context.
+pub(crate) struct Deny;
+
+impl<A, Op, Ctx> Guard<A, Op, Ctx> for Deny {
+    fn attempt(&self, _actor: &A, _op: Op, _ctx: &Ctx) -> Result<(), AccessDenied> {
+        Err(AccessDenied)
+    }
+}
+
+/// A `Caucus` with no actors, all-`Allow` guards, and an empty
+/// `ChannelTransport`, for tests that only care about a single
+/// guard/cap/rate-limiter in isolation rather than the broadcast
+/// pipeline itself.
+pub(crate) fn test_caucus<M: Clone + 'static>() -> Caucus<String, M> {
+    Caucus {
+        join_guard: Box::new(Allow),
+        send_guard: Box::new(Allow),
+        receive_guard: Box::new(Allow),
+        actors: Vec::new(),
+        transport: Box::new(ChannelTransport::new()),
+        last_sequence: RefCell::new(HashMap::new()),
+        known_keys: RefCell::new(HashMap::new()),
+    }
+}
+
+/// A `Dataspace` with all-`Allow` guards and an empty
+/// `ChannelTransport`.
+pub(crate) fn test_dataspace<M: Clone + 'static>() -> Dataspace<String, M> {
+    Dataspace::new(
+        Box::new(Allow),
+        Box::new(Allow),
+        Box::new(ChannelTransport::new()),
+    )
+}
@@ -0,0 +1,146 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Delivery of messages to the actors participating in a [`Caucus`](super::Caucus).
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// An error delivering a message to a recipient.
+#[derive(Debug)]
+pub enum DeliveryError {
+    /// The recipient is not known to this transport.
+    UnknownRecipient,
+    /// The recipient's channel has been disconnected.
+    Disconnected,
+}
+
+/// A way of actually getting a message to an actor.
+///
+/// Type Parameters:
+///
+/// * `A`: The type of actor participating in the caucus.
+/// * `M`: The type of messages exchanged over the caucus.
+pub trait Transport<A, M> {
+    /// Deliver `message` to `recipient`.
+    fn deliver(&self, recipient: &A, message: &M) -> Result<(), DeliveryError>;
+}
+
+/// An in-process [`Transport`] backed by one channel per actor.
+pub struct ChannelTransport<A, M> {
+    senders: HashMap<A, Sender<M>>,
+}
+
+impl<A: Eq + Hash, M: Clone> ChannelTransport<A, M> {
+    /// Create an empty transport with no registered actors.
+    pub fn new() -> Self {
+        ChannelTransport {
+            senders: HashMap::new(),
+        }
+    }
+
+    /// Register `actor` with this transport, returning the receiving
+    /// end of its channel.
+    pub fn register(&mut self, actor: A) -> EventStream<M> {
+        let (tx, rx) = mpsc::channel();
+        self.senders.insert(actor, tx);
+        EventStream { rx }
+    }
+
+    /// Stop delivering messages to `actor`.
+    pub fn unregister(&mut self, actor: &A) {
+        self.senders.remove(actor);
+    }
+}
+
+impl<A: Eq + Hash, M: Clone> Default for ChannelTransport<A, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Eq + Hash, M: Clone> Transport<A, M> for ChannelTransport<A, M> {
+    fn deliver(&self, recipient: &A, message: &M) -> Result<(), DeliveryError> {
+        self.senders
+            .get(recipient)
+            .ok_or(DeliveryError::UnknownRecipient)?
+            .send(message.clone())
+            .map_err(|_| DeliveryError::Disconnected)
+    }
+}
+
+/// The receiving half of an actor's channel, handed out by
+/// [`ChannelTransport::register`].
+pub struct EventStream<M> {
+    rx: Receiver<M>,
+}
+
+impl<M> EventStream<M> {
+    /// Block until the next message arrives, or the transport stops
+    /// holding this actor's sender (it is dropped, or the actor is
+    /// unregistered or registered again).
+    pub fn recv(&self) -> Result<M, DeliveryError> {
+        self.rx.recv().map_err(|_| DeliveryError::Disconnected)
+    }
+
+    /// Return a message if one is already waiting, without blocking.
+    pub fn try_recv(&self) -> Option<M> {
+        self.rx.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registered_actor_receives_delivered_messages() {
+        let mut transport = ChannelTransport::new();
+        let alice = transport.register("alice".to_string());
+
+        transport
+            .deliver(&"alice".to_string(), &"hello".to_string())
+            .unwrap();
+
+        assert_eq!(alice.try_recv(), Some("hello".to_string()));
+        assert_eq!(alice.try_recv(), None);
+    }
+
+    #[test]
+    fn delivering_to_an_unregistered_actor_fails() {
+        let transport: ChannelTransport<String, String> = ChannelTransport::new();
+
+        assert!(matches!(
+            transport.deliver(&"alice".to_string(), &"hello".to_string()),
+            Err(DeliveryError::UnknownRecipient)
+        ));
+    }
+
+    #[test]
+    fn unregistering_stops_delivery() {
+        let mut transport = ChannelTransport::new();
+        let _alice = transport.register("alice".to_string());
+        transport.unregister(&"alice".to_string());
+
+        assert!(matches!(
+            transport.deliver(&"alice".to_string(), &"hello".to_string()),
+            Err(DeliveryError::UnknownRecipient)
+        ));
+    }
+
+    #[test]
+    fn dropping_the_stream_disconnects_delivery() {
+        let mut transport = ChannelTransport::new();
+        let alice = transport.register("alice".to_string());
+        drop(alice);
+
+        assert!(matches!(
+            transport.deliver(&"alice".to_string(), &"hello".to_string()),
+            Err(DeliveryError::Disconnected)
+        ));
+    }
+}